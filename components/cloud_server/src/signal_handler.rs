@@ -1,18 +1,59 @@
 // Copyright 2017 TiKV Project Authors. Licensed under Apache-2.0.
 
-pub use self::imp::wait_for_signal;
+pub use self::imp::{wait_for_signal, DiagnosticsDest};
 
 #[cfg(unix)]
 mod imp {
+    use std::fs::OpenOptions;
+    use std::io::Write as _;
+    use std::path::PathBuf;
+
     use engine_traits::{Engines, MiscExt, RaftEngine};
     use libc::c_int;
     use nix::sys::signal::{SIGHUP, SIGINT, SIGTERM, SIGUSR1, SIGUSR2};
-    use rfstore::ServerRaftStoreRouter;
+    use rfstore::RaftRouter;
     use signal::trap::Trap;
+    use slog_global::{info, warn};
     use tikv_util::metrics;
+    use tikv_util::time::UnixSecs;
+
+    /// Where a signal-triggered diagnostics dump should be written: appended
+    /// to the server log, or written to a fresh timestamped file under the
+    /// data dir.
+    #[derive(Clone)]
+    pub enum DiagnosticsDest {
+        Log,
+        DataDir(PathBuf),
+    }
 
+    fn emit(dest: &DiagnosticsDest, name: &str, body: &str) {
+        match dest {
+            DiagnosticsDest::Log => info!("{}", body),
+            DiagnosticsDest::DataDir(dir) => {
+                let path = dir.join(format!("{}-{}.diag", name, UnixSecs::now().into_inner()));
+                match OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(&path)
+                {
+                    Ok(mut f) => match f.write_all(body.as_bytes()) {
+                        Ok(()) => info!("wrote {} diagnostics dump to {:?}", name, path),
+                        Err(e) => warn!("failed to write diagnostics dump to {:?}: {:?}", path, e),
+                    },
+                    Err(e) => warn!("failed to create diagnostics file {:?}: {:?}", path, e),
+                }
+            }
+        }
+    }
+
+    // TODO(x) update the server bootstrap call site for the new `router`/`dest` params.
     #[allow(dead_code)]
-    pub fn wait_for_signal(engines: Option<Engines<kvengine::Engine, rfengine::RFEngine>>) {
+    pub fn wait_for_signal(
+        engines: Option<Engines<kvengine::Engine, rfengine::RFEngine>>,
+        router: Option<RaftRouter>,
+        dest: DiagnosticsDest,
+    ) {
         let trap = Trap::trap(&[SIGTERM, SIGINT, SIGHUP, SIGUSR1, SIGUSR2]);
         for sig in trap {
             match sig {
@@ -21,13 +62,33 @@ mod imp {
                     break;
                 }
                 SIGUSR1 => {
-                    // Use SIGUSR1 to log metrics.
-                    // TODO(x)
-                    // info!("{}", metrics::dump());
-                    // if let Some(ref engines) = engines {
-                    //     info!("{:?}", MiscExt::dump_stats(&engines.kv));
-                    //     info!("{:?}", RaftEngine::dump_stats(&engines.raft));
-                    // }
+                    // Use SIGUSR1 to dump metrics and engine stats, so an
+                    // operator can grab a live snapshot of a stuck node
+                    // without attaching a debugger.
+                    let mut body = metrics::dump();
+                    if let Some(ref engines) = engines {
+                        body.push_str(&format!("\nkv engine stats: {:?}\n", MiscExt::dump_stats(&engines.kv)));
+                        body.push_str(&format!(
+                            "\nraft engine stats: {:?}\n",
+                            RaftEngine::dump_stats(&engines.raft)
+                        ));
+                    }
+                    emit(&dest, "metrics", &body);
+                }
+                SIGUSR2 => {
+                    // Use SIGUSR2 to dump a structured per-region snapshot:
+                    // region epoch, leader/term, applied vs. committed
+                    // index, and pending command count.
+                    match &router {
+                        Some(router) => {
+                            let mut body = String::new();
+                            for diag in router.region_diagnostics() {
+                                body.push_str(&format!("{:?}\n", diag));
+                            }
+                            emit(&dest, "regions", &body);
+                        }
+                        None => info!("SIGUSR2 received but no raft router is attached, skipping dump"),
+                    }
                 }
                 // TODO: handle more signal
                 _ => unreachable!(),
@@ -38,8 +99,22 @@ mod imp {
 
 #[cfg(not(unix))]
 mod imp {
+    use std::path::PathBuf;
+
     use engine_rocks::RocksEngine;
     use engine_traits::Engines;
+    use rfstore::RaftRouter;
 
-    pub fn wait_for_signal(_: Option<Engines<RocksEngine, RocksEngine>>) {}
+    #[derive(Clone)]
+    pub enum DiagnosticsDest {
+        Log,
+        DataDir(PathBuf),
+    }
+
+    pub fn wait_for_signal(
+        _: Option<Engines<RocksEngine, RocksEngine>>,
+        _: Option<RaftRouter>,
+        _: DiagnosticsDest,
+    ) {
+    }
 }
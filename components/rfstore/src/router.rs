@@ -25,7 +25,7 @@ pub trait RaftStoreRouter: StoreRouter + ProposalRouter + CasualRouter + Send +
     fn significant_send(&self, region_id: u64, msg: SignificantMsg) -> RaftStoreResult<()>;
 
     /// Broadcast a message generated by `msg_gen` to all Raft groups.
-    fn broadcast_normal(&self, msg_gen: impl FnMut() -> PeerMsg);
+    fn broadcast_normal(&self, msg_gen: impl Fn() -> PeerMsg + Send + Sync + 'static);
 
     /// Send a casual message to the given region.
     fn send_casual_msg(&self, region_id: u64, msg: CasualMessage) -> RaftStoreResult<()> {
@@ -42,6 +42,13 @@ pub trait RaftStoreRouter: StoreRouter + ProposalRouter + CasualRouter + Send +
         send_command_impl(self, req, cb, None)
     }
 
+    /// Like [`send_command`](RaftStoreRouter::send_command), but rejected
+    /// with `DeadlineExceeded` up front if `deadline` has already passed
+    /// by the time it's enqueued. This only covers the enqueue boundary:
+    /// a command that's still fresh here can still go stale while queued
+    /// for propose or apply, since re-checking at those boundaries needs
+    /// propose/apply-side plumbing that isn't in this tree (see
+    /// [`is_deadline_exceeded`]).
     fn send_command_with_deadline(
         &self,
         req: RaftCmdRequest,
@@ -66,11 +73,22 @@ pub trait RaftStoreRouter: StoreRouter + ProposalRouter + CasualRouter + Send +
 }
 
 pub trait LocalReadRouter: Send + Clone {
+    /// Serves `req`. Ordinary reads require this peer to be leader and are
+    /// served by the local `LocalReader`. Passing `replica_read: true`
+    /// asks for a follower read via the read-index protocol instead, but
+    /// that protocol isn't wired up yet (see
+    /// [`ServerRaftStoreRouter::read_index`]), so it currently always
+    /// fails (with `NotLeader` when the region is registered here)
+    /// rather than actually serving the read. `deadline`, if set, is
+    /// checked once up front on the replica-read path only; leader reads
+    /// ignore it.
     fn read(
         &self,
         read_id: Option<ThreadReadId>,
         req: RaftCmdRequest,
         cb: Callback,
+        replica_read: bool,
+        deadline: Option<Deadline>,
     ) -> RaftStoreResult<()>;
 }
 
@@ -78,6 +96,7 @@ pub trait LocalReadRouter: Send + Clone {
 pub struct ServerRaftStoreRouter {
     router: RaftRouter,
     local_reader: RefCell<LocalReader>,
+    transport: Arc<dyn Transport>,
 }
 
 impl Clone for ServerRaftStoreRouter {
@@ -85,17 +104,26 @@ impl Clone for ServerRaftStoreRouter {
         ServerRaftStoreRouter {
             router: self.router.clone(),
             local_reader: self.local_reader.clone(),
+            transport: self.transport.clone(),
         }
     }
 }
 
 impl ServerRaftStoreRouter {
-    /// Creates a new router.
-    pub fn new(router: RaftRouter, reader: LocalReader) -> ServerRaftStoreRouter {
+    /// Creates a new router. `transport` is used to ship `RaftMessage`s
+    /// that the local `RaftRouter` hands back as `RegionNotFound`, i.e.
+    /// whose target peer lives on another store or whose local region is
+    /// closed.
+    pub fn new(
+        router: RaftRouter,
+        reader: LocalReader,
+        transport: Arc<dyn Transport>,
+    ) -> ServerRaftStoreRouter {
         let local_reader = RefCell::new(reader);
         ServerRaftStoreRouter {
             router,
             local_reader,
+            transport,
         }
     }
 }
@@ -120,7 +148,25 @@ impl CasualRouter for ServerRaftStoreRouter {
 
 impl RaftStoreRouter for ServerRaftStoreRouter {
     fn send_raft_msg(&self, msg: RaftMessage) -> RaftStoreResult<()> {
-        RaftStoreRouter::send_raft_msg(&self.router, msg)
+        // Regions hosted locally are routed in-process; anything else has
+        // its target store resolved from the message and handed to the
+        // transport, which dials and reconnects as needed.
+        match RaftStoreRouter::send_raft_msg(&self.router, msg) {
+            Err(RegionNotFound(_, Some(msg))) => {
+                let store_id = msg.get_to_peer().get_store_id();
+                let result = self.transport.send(store_id, msg);
+                if result.is_err() && self.transport.is_persistently_unreachable(store_id) {
+                    // The store has stayed down across several consecutive
+                    // failures, not just missed one send; tell local peers
+                    // whose leader lives there the same way we already do
+                    // for other unreachable-store signals, instead of
+                    // broadcasting on every message sent during a blip.
+                    let _ = self.report_unreachable(store_id);
+                }
+                result
+            }
+            other => other,
+        }
     }
 
     /// Sends a significant message. We should guarantee that the message can't be dropped.
@@ -128,7 +174,7 @@ impl RaftStoreRouter for ServerRaftStoreRouter {
         RaftStoreRouter::significant_send(&self.router, region_id, msg)
     }
 
-    fn broadcast_normal(&self, msg_gen: impl FnMut() -> PeerMsg) {
+    fn broadcast_normal(&self, msg_gen: impl Fn() -> PeerMsg + Send + Sync + 'static) {
         self.router.broadcast_normal(msg_gen)
     }
 }
@@ -139,16 +185,84 @@ impl LocalReadRouter for ServerRaftStoreRouter {
         read_id: Option<ThreadReadId>,
         req: RaftCmdRequest,
         cb: Callback,
+        replica_read: bool,
+        deadline: Option<Deadline>,
     ) -> RaftStoreResult<()> {
+        if replica_read {
+            return self.read_index(req, cb, deadline);
+        }
         let mut local_reader = self.local_reader.borrow_mut();
         local_reader.read(read_id, req, cb);
         Ok(())
     }
 }
 
+impl ServerRaftStoreRouter {
+    // TODO(x): the read-index protocol — asking the leader to confirm a
+    // quorum of heartbeat acks for the current term, capturing the
+    // resulting commit index as the read index only once that
+    // confirmation lands, and waiting (bounded by `deadline`) for this
+    // peer's applied index to catch up — needs a peer-side
+    // `SignificantMsg` handler that isn't in this tree. Rather than
+    // forward a request to a handler nothing consumes (which would leave
+    // `cb` pending forever), this conservatively fails every replica read
+    // the same way as "no leader known", so callers fall back to a
+    // leader read instead of hanging.
+    fn read_index(
+        &self,
+        req: RaftCmdRequest,
+        _cb: Callback,
+        deadline: Option<Deadline>,
+    ) -> RaftStoreResult<()> {
+        if let Some(ref deadline) = deadline {
+            if deadline.is_exceeded() {
+                return Err(RaftStoreError::DeadlineExceeded);
+            }
+        }
+        let region_id = req.get_header().get_region_id();
+        self.router
+            .get(region_id)
+            .ok_or(RegionNotFound(region_id, None))?;
+        Err(RaftStoreError::NotLeader(region_id))
+    }
+}
+
+/// Ships a `RaftMessage` to a peer that lives on another store.
+/// `RaftRouter` only ever routes to peers registered locally; anything
+/// whose target store isn't us goes through a `Transport` instead, which
+/// owns dialing, reconnecting and buffering for the physical link.
+pub trait Transport: Send + Sync {
+    /// Sends `msg` towards `store_id`, dialing lazily and buffering as
+    /// needed. Returns an error if the message couldn't be delivered right
+    /// now; the implementation may still have buffered it for a later
+    /// attempt.
+    fn send(&self, store_id: u64, msg: RaftMessage) -> RaftStoreResult<()>;
+
+    /// Flushes any messages buffered for `store_id`.
+    fn flush(&self, store_id: u64);
+
+    /// Whether `store_id` has failed enough consecutive times to be
+    /// considered persistently unreachable, as opposed to a transient
+    /// blip. Callers use this to gate `report_unreachable` so a single
+    /// flaky send doesn't broadcast `StoreUnreachable` to every local
+    /// peer. Defaults to `false` for transports that don't track failure
+    /// streaks.
+    fn is_persistently_unreachable(&self, _store_id: u64) -> bool {
+        false
+    }
+}
+
 #[derive(Clone)]
 pub struct RaftStoreBlackHole;
 
+impl Transport for RaftStoreBlackHole {
+    fn send(&self, _: u64, _: RaftMessage) -> RaftStoreResult<()> {
+        Ok(())
+    }
+
+    fn flush(&self, _: u64) {}
+}
+
 impl CasualRouter for RaftStoreBlackHole {
     fn send(&self, _: u64, _: CasualMessage) -> RaftStoreResult<()> {
         Ok(())
@@ -176,7 +290,7 @@ impl RaftStoreRouter for RaftStoreBlackHole {
         Ok(())
     }
 
-    fn broadcast_normal(&self, _: impl FnMut() -> PeerMsg) {}
+    fn broadcast_normal(&self, _: impl Fn() -> PeerMsg + Send + Sync + 'static) {}
 }
 
 #[derive(Clone)]
@@ -184,17 +298,257 @@ pub struct RaftRouter {
     pub(crate) store_sender: Sender<StoreMsg>,
     pub(crate) peers: Arc<dashmap::DashMap<u64, PeerStates>>,
     pub(crate) peer_sender: Sender<(u64, PeerMsg)>,
+    /// This store's own id, so `send_raft_msg` can tell "not registered
+    /// here yet, but ours" (buffer it) apart from "belongs to another
+    /// store entirely" (leave it as `RegionNotFound` for the caller's
+    /// transport to ship out), instead of mixing the two up.
+    store_id: u64,
+    /// Messages for a region that isn't registered here yet, kept around
+    /// so `register()` can replay them instead of the sender's message
+    /// being silently dropped by a split/merge or peer-creation race.
+    pending: Arc<dashmap::DashMap<u64, Vec<PendingMsg>>>,
+    pending_cap: usize,
+    pending_ttl: std::time::Duration,
+    dropped: Arc<std::sync::atomic::AtomicU64>,
+    drop_hook: Arc<std::sync::RwLock<Option<DropHook>>>,
+    /// `broadcast_normal` stays single-threaded below this many peers, to
+    /// avoid worker-pool overhead on small stores.
+    broadcast_parallel_threshold: usize,
+    /// Persistent worker pool `broadcast_normal` fans chunks of peer ids
+    /// out to once the peer count crosses `broadcast_parallel_threshold`.
+    /// Workers are long-lived: they block on their channel instead of
+    /// being spawned fresh per broadcast, and exit on their own once the
+    /// last `RaftRouter` clone sharing this `Arc` (and with it the pool's
+    /// one `Sender` per worker) has been dropped.
+    broadcast_pool: Arc<Vec<std::sync::mpsc::Sender<BroadcastJob>>>,
+    /// Dropping this stops the pending-message sweep thread; see
+    /// [`RaftRouter::shutdown`].
+    sweeper_stop: Arc<std::sync::Mutex<Option<std::sync::mpsc::Sender<()>>>>,
+}
+
+/// One chunk of a broadcast: generate and send a message for each peer id
+/// in the chunk.
+type BroadcastJob = Box<dyn FnOnce() + Send>;
+
+/// Spawns `workers` long-lived threads, each running jobs handed to it
+/// over its own channel until every sender for that channel is dropped.
+fn spawn_broadcast_pool(workers: usize) -> Vec<std::sync::mpsc::Sender<BroadcastJob>> {
+    (0..workers.max(1))
+        .map(|i| {
+            let (tx, rx) = std::sync::mpsc::channel::<BroadcastJob>();
+            std::thread::Builder::new()
+                .name(format!("raft-router-broadcast-{}", i))
+                .spawn(move || {
+                    for job in rx {
+                        job();
+                    }
+                })
+                .expect("failed to spawn raft-router broadcast worker thread");
+            tx
+        })
+        .collect()
+}
+
+/// Just enough of `RaftRouter` for the pending-message sweep thread, so
+/// that thread doesn't have to hold its own clone of `peer_sender` /
+/// `store_sender` for the life of the process.
+#[derive(Clone)]
+struct PendingSweepHandle {
+    pending: Arc<dashmap::DashMap<u64, Vec<PendingMsg>>>,
+    pending_ttl: std::time::Duration,
+    dropped: Arc<std::sync::atomic::AtomicU64>,
+    drop_hook: Arc<std::sync::RwLock<Option<DropHook>>>,
+}
+
+impl PendingSweepHandle {
+    fn report_drop(&self, id: u64, reason: DropReason, msg: &PeerMsg) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        if let Some(hook) = self.drop_hook.read().unwrap().as_ref() {
+            hook(id, reason, peer_msg_kind(msg));
+        }
+    }
+
+    /// Drops every pending entry older than `pending_ttl`, across all
+    /// region ids, reporting each one through the drop hook.
+    fn sweep_expired(&self) {
+        let ttl = self.pending_ttl;
+        self.pending.retain(|&id, bucket| {
+            let (alive, expired): (Vec<_>, Vec<_>) = std::mem::take(bucket)
+                .into_iter()
+                .partition(|p| p.inserted_at.elapsed() <= ttl);
+            for p in expired {
+                self.report_drop(id, DropReason::NeverRegistered, &p.msg);
+            }
+            *bucket = alive;
+            !bucket.is_empty()
+        });
+    }
+}
+
+struct PendingMsg {
+    msg: PeerMsg,
+    inserted_at: std::time::Instant,
+}
+
+/// Why `RaftRouter` genuinely dropped a message, as opposed to buffering
+/// it for later replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// The region was registered but has since been closed (e.g. split
+    /// away, merged, or destroyed).
+    Closed,
+    /// No peer for this region is registered on this store. `close` removes
+    /// the peer from the map, so a message sent to a region after it has
+    /// been closed is reported with this reason too, not as `Closed`.
+    NeverRegistered,
+}
+
+type DropHook = Arc<dyn Fn(u64, DropReason, &'static str) + Send + Sync>;
+
+const DEFAULT_PENDING_BUFFER_SIZE: usize = 64;
+const DEFAULT_PENDING_BUFFER_TTL: std::time::Duration = std::time::Duration::from_secs(10);
+/// Below this many registered peers, `broadcast_normal` just iterates on
+/// the caller's thread; a worker pool only pays for itself on stores
+/// hosting a lot of regions.
+const DEFAULT_BROADCAST_PARALLEL_THRESHOLD: usize = 2048;
+
+fn peer_msg_kind(msg: &PeerMsg) -> &'static str {
+    match msg {
+        PeerMsg::RaftMessage(_) => "raft_message",
+        PeerMsg::SignificantMsg(_) => "significant_msg",
+        PeerMsg::CasualMessage(_) => "casual_message",
+        _ => "other",
+    }
 }
 
 impl RaftRouter {
-    pub(crate) fn new(peer_sender: Sender<(u64, PeerMsg)>, store_sender: Sender<StoreMsg>) -> Self {
-        Self {
+    pub(crate) fn new(
+        peer_sender: Sender<(u64, PeerMsg)>,
+        store_sender: Sender<StoreMsg>,
+        store_id: u64,
+    ) -> Self {
+        let router = Self {
             store_sender,
             peers: Arc::new(dashmap::DashMap::new()),
             peer_sender,
+            store_id,
+            pending: Arc::new(dashmap::DashMap::new()),
+            pending_cap: DEFAULT_PENDING_BUFFER_SIZE,
+            pending_ttl: DEFAULT_PENDING_BUFFER_TTL,
+            dropped: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            drop_hook: Arc::new(std::sync::RwLock::new(None)),
+            broadcast_parallel_threshold: DEFAULT_BROADCAST_PARALLEL_THRESHOLD,
+            broadcast_pool: Arc::new(spawn_broadcast_pool(
+                (tikv_util::sys::SysQuota::cpu_cores_quota() as usize).max(1),
+            )),
+            sweeper_stop: Arc::new(std::sync::Mutex::new(None)),
+        };
+        router.spawn_pending_sweeper();
+        router
+    }
+
+    /// Periodically drops (and reports) buffered messages that have
+    /// outlived the pending-buffer TTL without their region ever being
+    /// registered. Without this, a region that never appears on this
+    /// store would keep its pending bucket alive forever, since the only
+    /// other eviction points (`buffer_pending`, `register`) only run
+    /// again for the *same* region id.
+    ///
+    /// Blocks on a channel rather than sleeping in a loop, so [`shutdown`]
+    /// can wake and stop it immediately instead of waiting out the sweep
+    /// interval, and carries only the sweep-relevant state rather than a
+    /// full router clone, so it doesn't pin `peer_sender` / `store_sender`
+    /// open for the life of the process.
+    ///
+    /// [`shutdown`]: RaftRouter::shutdown
+    fn spawn_pending_sweeper(&self) {
+        let handle = PendingSweepHandle {
+            pending: self.pending.clone(),
+            pending_ttl: self.pending_ttl,
+            dropped: self.dropped.clone(),
+            drop_hook: self.drop_hook.clone(),
+        };
+        let interval = self.pending_ttl.max(std::time::Duration::from_millis(100));
+        let (tx, rx) = std::sync::mpsc::channel();
+        *self.sweeper_stop.lock().unwrap() = Some(tx);
+        std::thread::Builder::new()
+            .name("raft-router-pending-sweep".to_string())
+            .spawn(move || loop {
+                match rx.recv_timeout(interval) {
+                    Ok(()) | Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => handle.sweep_expired(),
+                }
+            })
+            .expect("failed to spawn raft-router pending sweep thread");
+    }
+
+    /// Stops the pending-message sweep thread. Safe to call more than
+    /// once, and from any clone of this router.
+    pub fn shutdown(&self) {
+        self.sweeper_stop.lock().unwrap().take();
+    }
+
+    /// Overrides the default size and TTL of the per-region pending-message
+    /// buffer used to survive register() races.
+    pub fn set_pending_buffer_config(&mut self, cap: usize, ttl: std::time::Duration) {
+        self.pending_cap = cap;
+        self.pending_ttl = ttl;
+        // The sweep thread holds its own copy of the TTL; restart it so it
+        // sweeps with the new one.
+        self.shutdown();
+        self.spawn_pending_sweeper();
+    }
+
+    /// Overrides the peer-count threshold at or above which `broadcast_normal`
+    /// fans out across `workers` threads instead of running on the
+    /// caller's thread.
+    pub fn set_broadcast_parallelism(&mut self, threshold: usize, workers: usize) {
+        self.broadcast_parallel_threshold = threshold;
+        self.broadcast_pool = Arc::new(spawn_broadcast_pool(workers.max(1)));
+    }
+
+    /// Installs a hook invoked with `(region_id, reason, message kind)` for
+    /// every message this router genuinely drops.
+    pub fn set_drop_hook(&self, hook: impl Fn(u64, DropReason, &'static str) + Send + Sync + 'static) {
+        *self.drop_hook.write().unwrap() = Some(Arc::new(hook));
+    }
+
+    /// Number of messages dropped (not merely buffered for replay) since
+    /// this router was created.
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn report_drop(&self, id: u64, reason: DropReason, msg: &PeerMsg) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        if let Some(hook) = self.drop_hook.read().unwrap().as_ref() {
+            hook(id, reason, peer_msg_kind(msg));
         }
     }
 
+    /// Buffers `msg` for `id`, evicting expired and, if still over
+    /// capacity, the oldest entries first (reporting those as genuinely
+    /// dropped).
+    fn buffer_pending(&self, id: u64, msg: PeerMsg) {
+        let mut bucket = self.pending.entry(id).or_insert_with(Vec::new);
+        let ttl = self.pending_ttl;
+        let (alive, expired): (Vec<_>, Vec<_>) = std::mem::take(&mut *bucket)
+            .into_iter()
+            .partition(|p| p.inserted_at.elapsed() <= ttl);
+        for p in expired {
+            self.report_drop(id, DropReason::NeverRegistered, &p.msg);
+        }
+        *bucket = alive;
+        while bucket.len() >= self.pending_cap {
+            let evicted = bucket.remove(0);
+            self.report_drop(id, DropReason::NeverRegistered, &evicted.msg);
+        }
+        bucket.push(PendingMsg {
+            msg,
+            inserted_at: std::time::Instant::now(),
+        });
+    }
+
     pub(crate) fn get(&self, region_id: u64) -> Option<dashmap::mapref::one::Ref<u64, PeerStates>> {
         self.peers.get(&region_id)
     }
@@ -211,6 +565,16 @@ impl RaftRouter {
         let applier = Applier::new_from_peer(&peer);
         let new_peer = PeerStates::new(applier, peer);
         self.peers.insert(id, new_peer);
+        if let Some((_, bucket)) = self.pending.remove(&id) {
+            let ttl = self.pending_ttl;
+            for pending in bucket {
+                if pending.inserted_at.elapsed() <= ttl {
+                    self.peer_sender.send((id, pending.msg));
+                } else {
+                    self.report_drop(id, DropReason::NeverRegistered, &pending.msg);
+                }
+            }
+        }
     }
 
     pub(crate) fn close(&self, id: u64) {
@@ -219,28 +583,91 @@ impl RaftRouter {
             self.peers
                 .remove(&peer.peer_fsm.lock().unwrap().peer.region_id);
         }
+        // A pending bucket for this id is no longer useful once we know
+        // the region is closed; drop it now rather than waiting on the
+        // periodic sweeper.
+        if let Some((_, bucket)) = self.pending.remove(&id) {
+            for pending in bucket {
+                self.report_drop(id, DropReason::Closed, &pending.msg);
+            }
+        }
     }
 
-    pub(crate) fn send(&self, id: u64, mut msg: PeerMsg) -> RaftStoreResult<()> {
+    pub(crate) fn send(&self, id: u64, msg: PeerMsg) -> RaftStoreResult<()> {
         if let Some(peer) = self.peers.get(&id) {
             if !peer.closed.load(Ordering::Relaxed) {
                 self.peer_sender.send((id, msg));
                 return Ok(());
             }
+            drop(peer);
+            self.report_drop(id, DropReason::Closed, &msg);
+            return Err(RegionNotFound(id, Some(msg)));
         }
+        self.report_drop(id, DropReason::NeverRegistered, &msg);
         Err(RegionNotFound(id, Some(msg)))
     }
 
     pub(crate) fn send_store(&self, msg: StoreMsg) {
         self.store_sender.send(msg);
     }
+
+    /// Collects a point-in-time diagnostic snapshot of every peer
+    /// registered on this store, for dumping on e.g. SIGUSR2 so an
+    /// operator can see what a stuck node's Raft groups are doing
+    /// without attaching a debugger.
+    pub fn region_diagnostics(&self) -> Vec<RegionDiagnostic> {
+        self.peers
+            .iter()
+            .map(|entry| {
+                let fsm = entry.value().peer_fsm.lock().unwrap();
+                let peer = &fsm.peer;
+                RegionDiagnostic {
+                    region_id: *entry.key(),
+                    epoch: peer.region().get_region_epoch().clone(),
+                    is_leader: peer.is_leader(),
+                    term: peer.term(),
+                    applied_index: peer.get_store().applied_index(),
+                    committed_index: peer.get_store().committed_index(),
+                    pending_commands: fsm.pending_cmd_count(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A point-in-time snapshot of one peer's Raft progress, used by the
+/// SIGUSR2 diagnostics dump.
+#[derive(Debug)]
+pub struct RegionDiagnostic {
+    pub region_id: u64,
+    pub epoch: kvproto::metapb::RegionEpoch,
+    pub is_leader: bool,
+    pub term: u64,
+    pub applied_index: u64,
+    pub committed_index: u64,
+    pub pending_commands: usize,
 }
 
 impl RaftStoreRouter for RaftRouter {
     fn send_raft_msg(&self, msg: RaftMessage) -> RaftStoreResult<()> {
         let region_id = msg.get_region_id();
-        let raft_msg = PeerMsg::RaftMessage(msg);
-        self.send(region_id, raft_msg)
+        if msg.get_to_peer().get_store_id() != self.store_id {
+            // Not ours: leave this as `RegionNotFound` so
+            // `ServerRaftStoreRouter::send_raft_msg` falls back to the
+            // transport instead of the message being buffered here
+            // forever, since its peer will never be registered on this
+            // store.
+            return Err(RegionNotFound(region_id, Some(PeerMsg::RaftMessage(msg))));
+        }
+        if self.peers.get(&region_id).is_none() {
+            // Ours, but the peer may simply not have been registered yet
+            // (a message can race ahead of region creation during
+            // split/merge). Buffer it so register() can replay it,
+            // instead of dropping it outright.
+            self.buffer_pending(region_id, PeerMsg::RaftMessage(msg));
+            return Ok(());
+        }
+        self.send(region_id, PeerMsg::RaftMessage(msg))
     }
 
     fn significant_send(&self, region_id: u64, msg: SignificantMsg) -> RaftStoreResult<()> {
@@ -248,29 +675,425 @@ impl RaftStoreRouter for RaftRouter {
         self.send(region_id, msg)
     }
 
-    fn broadcast_normal(&self, mut msg_gen: impl FnMut() -> PeerMsg) {
-        for peer in self.peers.iter() {
-            let msg = msg_gen();
-            self.peer_sender.send((*peer.key(), msg));
+    fn broadcast_normal(&self, msg_gen: impl Fn() -> PeerMsg + Send + Sync + 'static) {
+        if self.peers.len() < self.broadcast_parallel_threshold {
+            for peer in self.peers.iter() {
+                let msg = msg_gen();
+                self.peer_sender.send((*peer.key(), msg));
+            }
+            return;
+        }
+        // Large stores can host tens of thousands of regions; iterating
+        // the whole DashMap serially on the caller's thread turns a
+        // broadcast (e.g. StoreUnreachable during failover) into a
+        // latency spike. Fan the peer ids out across the persistent
+        // broadcast worker pool instead; msg_gen is `Fn`, so each worker
+        // calls its own clone directly rather than contending on a mutex.
+        let ids: Vec<u64> = self.peers.iter().map(|e| *e.key()).collect();
+        let msg_gen = Arc::new(msg_gen);
+        let workers = self.broadcast_pool.len().max(1);
+        let chunk_size = (ids.len() + workers - 1) / workers;
+        for (i, chunk) in ids.chunks(chunk_size.max(1)).enumerate() {
+            let chunk = chunk.to_vec();
+            let peer_sender = self.peer_sender.clone();
+            let msg_gen = msg_gen.clone();
+            let job: BroadcastJob = Box::new(move || {
+                for id in chunk {
+                    let msg = msg_gen();
+                    peer_sender.send((id, msg));
+                }
+            });
+            let _ = self.broadcast_pool[i % self.broadcast_pool.len()].send(job);
         }
     }
 }
 
+/// Returns whether `deadline` has already passed.
+///
+/// This router only calls it at the enqueue boundary, in
+/// `send_command_impl` below. A deadline can also expire while a command
+/// sits queued for propose or while it waits to apply; catching that
+/// needs the peer's propose handler and the applier's apply loop (in
+/// `crate::store`, outside this router) to call this same helper again at
+/// their own boundaries.
+// TODO(x): wire up the propose/apply-boundary checks in crate::store;
+// until then a command that's fresh at enqueue can still apply stale.
+pub(crate) fn is_deadline_exceeded(deadline: &Option<Deadline>) -> bool {
+    deadline.as_ref().map_or(false, |d| d.is_exceeded())
+}
+
 fn send_command_impl(
     router: &impl ProposalRouter,
     req: RaftCmdRequest,
     cb: Callback,
     deadline: Option<Deadline>,
 ) -> RaftStoreResult<()> {
-    let mut cmd = RaftCommand::new(req, cb);
-    // TODO(x) handle deadline
+    // Reject here at the enqueue boundary if the deadline has already
+    // passed, instead of queueing a proposal we know will be stale by the
+    // time it's handled. This is the only boundary this router enforces;
+    // see is_deadline_exceeded's doc comment.
+    if is_deadline_exceeded(&deadline) {
+        return Err(RaftStoreError::DeadlineExceeded);
+    }
+    let cmd = RaftCommand::with_deadline(req, cb, deadline);
     router.send(cmd)
 }
 
+/// One established outbound connection to a remote store.
+pub trait RemoteSink: Send {
+    /// Sends `msg`. On failure, hands `msg` back alongside the error so
+    /// the caller can re-buffer it instead of it being silently lost.
+    fn send(&mut self, msg: RaftMessage) -> Result<(), (RaftStoreError, RaftMessage)>;
+    fn flush(&mut self);
+}
+
+/// Dials a remote store, producing a [`RemoteSink`] used to ship messages
+/// to it. Implemented by the gRPC client layer; kept separate from
+/// `ConnectionManager` so the reconnect/backoff/buffering logic here can be
+/// exercised without a real network.
+pub trait Dialer: Send + Sync + 'static {
+    fn dial(&self, store_id: u64) -> RaftStoreResult<Box<dyn RemoteSink>>;
+}
+
+const RECONNECT_MIN_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+const RECONNECT_MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(10);
+const MAX_PENDING_PER_STORE: usize = 4096;
+/// Consecutive dial/send failures before a store counts as persistently
+/// (not just transiently) unreachable. Gates `report_unreachable` so a
+/// single flaky send doesn't broadcast `StoreUnreachable` to every local
+/// peer; see [`Transport::is_persistently_unreachable`].
+const PERSISTENT_FAILURE_THRESHOLD: u32 = 3;
+
+struct StoreConn {
+    sink: Option<Box<dyn RemoteSink>>,
+    pending: std::collections::VecDeque<RaftMessage>,
+    next_dial_at: std::time::Instant,
+    backoff: std::time::Duration,
+    /// Consecutive dial/send failures since the last success.
+    consecutive_failures: u32,
+}
+
+impl StoreConn {
+    fn new() -> Self {
+        StoreConn {
+            sink: None,
+            pending: std::collections::VecDeque::new(),
+            next_dial_at: std::time::Instant::now(),
+            backoff: RECONNECT_MIN_BACKOFF,
+            consecutive_failures: 0,
+        }
+    }
+}
+
+/// A [`Transport`] that maintains one lazily-dialed outbound connection per
+/// remote store and transparently re-dials with exponential backoff after
+/// a connection drops, so a flaky link doesn't turn into dropped Raft
+/// traffic.
+#[derive(Clone)]
+pub struct ConnectionManager<D: Dialer> {
+    dialer: Arc<D>,
+    conns: Arc<dashmap::DashMap<u64, std::sync::Mutex<StoreConn>>>,
+}
+
+impl<D: Dialer> ConnectionManager<D> {
+    pub fn new(dialer: D) -> Self {
+        ConnectionManager {
+            dialer: Arc::new(dialer),
+            conns: Arc::new(dashmap::DashMap::new()),
+        }
+    }
+
+    /// Dials `store_id` if there's no live connection and the backoff
+    /// window has elapsed.
+    fn ensure_conn(&self, conn: &mut StoreConn, store_id: u64) {
+        if conn.sink.is_some() || std::time::Instant::now() < conn.next_dial_at {
+            return;
+        }
+        match self.dialer.dial(store_id) {
+            Ok(sink) => {
+                conn.sink = Some(sink);
+                conn.backoff = RECONNECT_MIN_BACKOFF;
+            }
+            Err(e) => {
+                slog_global::warn!("failed to dial store {}: {:?}", store_id, e);
+                conn.next_dial_at = std::time::Instant::now() + conn.backoff;
+                conn.backoff = (conn.backoff * 2).min(RECONNECT_MAX_BACKOFF);
+                conn.consecutive_failures = conn.consecutive_failures.saturating_add(1);
+            }
+        }
+    }
+}
+
+impl<D: Dialer> Transport for ConnectionManager<D> {
+    fn send(&self, store_id: u64, msg: RaftMessage) -> RaftStoreResult<()> {
+        let entry = self
+            .conns
+            .entry(store_id)
+            .or_insert_with(|| std::sync::Mutex::new(StoreConn::new()));
+        let mut conn = entry.lock().unwrap();
+        let was_disconnected = conn.sink.is_none();
+        self.ensure_conn(&mut conn, store_id);
+        match conn.sink.as_mut() {
+            Some(sink) => {
+                // A (re)dial just happened: ship anything buffered while
+                // we were disconnected first, oldest first, so a
+                // reconnect doesn't reorder `msg` ahead of or strand
+                // messages that arrived before it.
+                if was_disconnected {
+                    while let Some(buffered) = conn.pending.pop_front() {
+                        if let Err((e, buffered)) = sink.send(buffered) {
+                            conn.sink = None;
+                            conn.consecutive_failures = conn.consecutive_failures.saturating_add(1);
+                            // Put the message that failed back at the
+                            // front, and requeue the new message behind
+                            // everything still pending rather than
+                            // sending it now, so neither is lost and
+                            // relative order is preserved across the
+                            // failure.
+                            conn.pending.push_front(buffered);
+                            conn.pending.push_back(msg);
+                            return Err(e);
+                        }
+                    }
+                }
+                match sink.send(msg) {
+                    Ok(()) => {
+                        conn.consecutive_failures = 0;
+                        Ok(())
+                    }
+                    Err((e, msg)) => {
+                        // The connection is presumed dead; drop it so the
+                        // next send dials a fresh one, and keep the
+                        // message that failed instead of losing it.
+                        conn.sink = None;
+                        conn.consecutive_failures = conn.consecutive_failures.saturating_add(1);
+                        conn.pending.push_back(msg);
+                        Err(e)
+                    }
+                }
+            }
+            None => {
+                if conn.pending.len() >= MAX_PENDING_PER_STORE {
+                    conn.pending.pop_front();
+                }
+                conn.pending.push_back(msg);
+                Err(RaftStoreError::StoreUnreachable(store_id))
+            }
+        }
+    }
+
+    fn flush(&self, store_id: u64) {
+        if let Some(entry) = self.conns.get(&store_id) {
+            let mut conn = entry.lock().unwrap();
+            if let Some(sink) = conn.sink.as_mut() {
+                while let Some(msg) = conn.pending.pop_front() {
+                    match sink.send(msg) {
+                        Ok(()) => conn.consecutive_failures = 0,
+                        Err((_, msg)) => {
+                            conn.pending.push_front(msg);
+                            break;
+                        }
+                    }
+                }
+                sink.flush();
+            }
+        }
+    }
+
+    /// A store counts as persistently unreachable once it has strung
+    /// together [`PERSISTENT_FAILURE_THRESHOLD`] consecutive dial/send
+    /// failures, as opposed to the first blip in an otherwise-healthy
+    /// link.
+    fn is_persistently_unreachable(&self, store_id: u64) -> bool {
+        self.conns
+            .get(&store_id)
+            .map(|entry| entry.lock().unwrap().consecutive_failures >= PERSISTENT_FAILURE_THRESHOLD)
+            .unwrap_or(false)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
     #[test]
     fn test_run() {
         println!("run")
     }
+
+    #[test]
+    fn deadline_exceeded_checks_only_when_set_and_past() {
+        assert!(!is_deadline_exceeded(&None));
+
+        let far_future = Deadline::from_now(Duration::from_secs(60));
+        assert!(!is_deadline_exceeded(&Some(far_future)));
+
+        let already_past = Deadline::from_now(Duration::from_millis(0));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(is_deadline_exceeded(&Some(already_past)));
+    }
+
+    struct RecordingSink {
+        sent: Arc<Mutex<Vec<RaftMessage>>>,
+    }
+
+    impl RemoteSink for RecordingSink {
+        fn send(&mut self, msg: RaftMessage) -> Result<(), (RaftStoreError, RaftMessage)> {
+            self.sent.lock().unwrap().push(msg);
+            Ok(())
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    /// A sink whose `send` always fails, handing `msg` back so the caller
+    /// can verify nothing is lost.
+    struct FailingSink;
+
+    impl RemoteSink for FailingSink {
+        fn send(&mut self, msg: RaftMessage) -> Result<(), (RaftStoreError, RaftMessage)> {
+            Err((RaftStoreError::StoreUnreachable(0), msg))
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    /// Fails to dial the first `fail_times` calls, then succeeds and hands
+    /// out a sink that records every message shipped through it.
+    struct FlakyDialer {
+        fail_times: AtomicUsize,
+        sent: Arc<Mutex<Vec<RaftMessage>>>,
+    }
+
+    impl Dialer for FlakyDialer {
+        fn dial(&self, store_id: u64) -> RaftStoreResult<Box<dyn RemoteSink>> {
+            if self.fail_times.load(Ordering::SeqCst) > 0 {
+                self.fail_times.fetch_sub(1, Ordering::SeqCst);
+                return Err(RaftStoreError::StoreUnreachable(store_id));
+            }
+            Ok(Box::new(RecordingSink {
+                sent: self.sent.clone(),
+            }))
+        }
+    }
+
+    fn msg_with_index(index: u64) -> RaftMessage {
+        let mut msg = RaftMessage::default();
+        msg.mut_message().set_index(index);
+        msg
+    }
+
+    /// Always dials successfully, but every send through the returned sink
+    /// fails until `fail_times` (shared across reconnects) reaches zero.
+    struct AlwaysDialer {
+        fail_times: Arc<AtomicUsize>,
+        sent: Arc<Mutex<Vec<RaftMessage>>>,
+    }
+
+    struct CountedFlakySink {
+        fail_times: Arc<AtomicUsize>,
+        sent: Arc<Mutex<Vec<RaftMessage>>>,
+    }
+
+    impl RemoteSink for CountedFlakySink {
+        fn send(&mut self, msg: RaftMessage) -> Result<(), (RaftStoreError, RaftMessage)> {
+            if self.fail_times.load(Ordering::SeqCst) > 0 {
+                self.fail_times.fetch_sub(1, Ordering::SeqCst);
+                return Err((RaftStoreError::StoreUnreachable(0), msg));
+            }
+            self.sent.lock().unwrap().push(msg);
+            Ok(())
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    impl Dialer for AlwaysDialer {
+        fn dial(&self, _store_id: u64) -> RaftStoreResult<Box<dyn RemoteSink>> {
+            Ok(Box::new(CountedFlakySink {
+                fail_times: self.fail_times.clone(),
+                sent: self.sent.clone(),
+            }))
+        }
+    }
+
+    #[test]
+    fn connection_manager_does_not_lose_or_reorder_on_drain_failure() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let dialer = AlwaysDialer {
+            fail_times: Arc::new(AtomicUsize::new(2)),
+            sent: sent.clone(),
+        };
+        let manager = ConnectionManager::new(dialer);
+
+        // Connects, but the send itself fails: message #1 is buffered.
+        manager.send(1, msg_with_index(1)).unwrap_err();
+        assert!(sent.lock().unwrap().is_empty());
+
+        // Reconnects, but draining buffered message #1 fails too. Neither
+        // #1 nor the new message #2 may be lost, and #1 must stay ahead
+        // of #2 since it arrived first.
+        manager.send(1, msg_with_index(2)).unwrap_err();
+        assert!(sent.lock().unwrap().is_empty());
+
+        let entry = manager.conns.get(&1).unwrap();
+        let conn = entry.lock().unwrap();
+        let pending: Vec<u64> = conn
+            .pending
+            .iter()
+            .map(|m| m.get_message().get_index())
+            .collect();
+        assert_eq!(pending, vec![1, 2]);
+    }
+
+    struct AlwaysFailDialer;
+
+    impl Dialer for AlwaysFailDialer {
+        fn dial(&self, _store_id: u64) -> RaftStoreResult<Box<dyn RemoteSink>> {
+            Ok(Box::new(FailingSink))
+        }
+    }
+
+    #[test]
+    fn connection_manager_gates_persistent_unreachable_on_consecutive_failures() {
+        let manager = ConnectionManager::new(AlwaysFailDialer);
+        assert!(!manager.is_persistently_unreachable(7));
+
+        for i in 0..PERSISTENT_FAILURE_THRESHOLD {
+            let _ = manager.send(7, msg_with_index(i as u64));
+            if i + 1 < PERSISTENT_FAILURE_THRESHOLD {
+                assert!(!manager.is_persistently_unreachable(7));
+            }
+        }
+        assert!(manager.is_persistently_unreachable(7));
+    }
+
+    #[test]
+    fn connection_manager_buffers_while_unreachable_then_drains_in_order_on_reconnect() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let dialer = FlakyDialer {
+            fail_times: AtomicUsize::new(1),
+            sent: sent.clone(),
+        };
+        let manager = ConnectionManager::new(dialer);
+
+        // The store is unreachable on the first attempt: the message is
+        // buffered rather than lost, and the caller sees `StoreUnreachable`.
+        let err = manager.send(1, msg_with_index(1)).unwrap_err();
+        assert!(matches!(err, RaftStoreError::StoreUnreachable(1)));
+        assert!(sent.lock().unwrap().is_empty());
+
+        // Once the backoff window passes, the next send reconnects and
+        // must drain the buffered message #1 before the new message #2,
+        // oldest first, instead of reordering or stranding it.
+        std::thread::sleep(RECONNECT_MIN_BACKOFF + Duration::from_millis(50));
+        manager.send(1, msg_with_index(2)).unwrap();
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].get_message().get_index(), 1);
+        assert_eq!(sent[1].get_message().get_index(), 2);
+    }
 }